@@ -0,0 +1,108 @@
+//! Live, hot-reloadable subset of `Config`: the rescale/remap targets, the
+//! OSC forwarding table, and which MIDI control drives the rescale fader.
+//! A SIGHUP handler re-reads the config file and atomically swaps these in
+//! without tearing down sockets or losing the current scale.
+
+use crate::{Config, MidiControl, UniverseActions};
+use anyhow::Result;
+use artnet_protocol::PortAddress;
+use log::{error, info};
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+};
+
+pub struct LiveConfig {
+    /// Raw universe table, keyed the same way `Config::universes` is; kept
+    /// around (in addition to `actions`) so anything that needs to describe
+    /// the currently configured universes by id, like the ArtPollReply
+    /// builder, reflects the latest reload rather than the startup config.
+    universes: RwLock<HashMap<u8, UniverseActions>>,
+    actions: RwLock<HashMap<PortAddress, UniverseActions>>,
+    osc_mappings: RwLock<HashMap<MidiControl, String>>,
+    rescale_control: RwLock<MidiControl>,
+}
+
+impl LiveConfig {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            universes: RwLock::new(config.universes.clone()),
+            actions: RwLock::new(config.actions()),
+            osc_mappings: RwLock::new(osc_mapping_table(config)),
+            rescale_control: RwLock::new(config.rescale_midi_control),
+        }
+    }
+
+    pub fn universes(&self) -> HashMap<u8, UniverseActions> {
+        self.universes.read().unwrap().clone()
+    }
+
+    pub fn action_for(&self, port: &PortAddress) -> Option<UniverseActions> {
+        self.actions.read().unwrap().get(port).cloned()
+    }
+
+    pub fn osc_address_for(&self, control: &MidiControl) -> Option<String> {
+        self.osc_mappings.read().unwrap().get(control).cloned()
+    }
+
+    pub fn rescale_control(&self) -> MidiControl {
+        *self.rescale_control.read().unwrap()
+    }
+
+    /// Re-read `path` and swap in its universes, OSC mappings, and rescale
+    /// control, logging which universes were added or removed.
+    fn reload(&self, path: &PathBuf) -> Result<()> {
+        let file = File::open(path)?;
+        let config: Config = serde_yaml::from_reader(file)?;
+        let new_actions = config.actions();
+
+        {
+            let old_actions = self.actions.read().unwrap();
+            let added: Vec<_> = new_actions
+                .keys()
+                .filter(|port| !old_actions.contains_key(port))
+                .collect();
+            let removed: Vec<_> = old_actions
+                .keys()
+                .filter(|port| !new_actions.contains_key(port))
+                .collect();
+            if !added.is_empty() || !removed.is_empty() {
+                info!("reload: universes added {added:?}, removed {removed:?}");
+            }
+        }
+
+        *self.universes.write().unwrap() = config.universes.clone();
+        *self.actions.write().unwrap() = new_actions;
+        *self.osc_mappings.write().unwrap() = osc_mapping_table(&config);
+        *self.rescale_control.write().unwrap() = config.rescale_midi_control;
+        Ok(())
+    }
+}
+
+fn osc_mapping_table(config: &Config) -> HashMap<MidiControl, String> {
+    config
+        .osc_forward
+        .mappings
+        .iter()
+        .map(|mapping| (mapping.midi, mapping.osc.clone()))
+        .collect()
+}
+
+/// Install a SIGHUP handler that reloads `path` into `live` on receipt.
+pub fn install_reload_handler(live: Arc<LiveConfig>, path: PathBuf) -> Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("received SIGHUP, reloading {}", path.display());
+            match live.reload(&path) {
+                Ok(()) => info!("reloaded config from {}", path.display()),
+                Err(err) => error!("failed to reload config from {}: {err}", path.display()),
+            }
+        }
+    });
+    Ok(())
+}
@@ -1,37 +1,84 @@
+mod live;
+mod tunnel;
+mod wizard;
+
 use anyhow::{anyhow, bail, Result};
 use artnet_protocol::*;
 use log::{debug, error, info, warn};
 use midir::{MidiIO, MidiInput, MidiInputConnection};
 use number::UnipolarFloat;
 use rosc::{encoder, OscMessage, OscPacket, OscType};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use simplelog::{Config as LogConfig, SimpleLogger};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env::args,
     fs::File,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
-    path::Path,
-    sync::mpsc::{channel, Sender},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc,
+    },
     thread,
 };
+use live::LiveConfig;
+use tunnel::Tunnel;
 
 const PORT: u16 = 6454;
 
 fn main() -> Result<()> {
-    let config_path = args().nth(1).unwrap();
-    let config_file = File::open(Path::new(&config_path))?;
+    SimpleLogger::init(log::LevelFilter::Info, LogConfig::default())?;
+
+    let mut rest = args().skip(1);
+    let first = rest.next().unwrap();
+
+    if first == "wizard" {
+        let output_path = rest.next().unwrap_or_else(|| "config.yaml".to_string());
+        return wizard::run(Path::new(&output_path));
+    }
+
+    let config_path = PathBuf::from(&first);
+    let config_file = File::open(&config_path)?;
     let config: Config = serde_yaml::from_reader(&config_file).unwrap();
 
-    SimpleLogger::init(log::LevelFilter::Info, LogConfig::default())?;
-    let socket = UdpSocket::bind(("0.0.0.0", PORT)).unwrap();
-    run_rescale(socket, config)
+    let socket = bind_output_socket(&config)?;
+    run_rescale(socket, config, config_path)
 }
 
-fn run_rescale(socket: UdpSocket, config: Config) -> Result<()> {
+/// Bind the socket used for both receiving local Art-Net and sending
+/// rescaled universes. Uses `socket2` so `SO_REUSEADDR`/`SO_REUSEPORT` let
+/// the rescaler coexist with other Art-Net applications on the host, and
+/// `SO_BROADCAST` so universes configured with a broadcast destination work.
+/// Also joins the multicast group of any universe configured with one.
+fn bind_output_socket(config: &Config) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(target_os = "solaris")))]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT)).into())?;
+
+    for destination in config.actions().values().map(|action| action.destination) {
+        if destination.is_multicast() {
+            socket.join_multicast_v4(&destination, &Ipv4Addr::UNSPECIFIED)?;
+        }
+    }
+
+    Ok(socket.into())
+}
+
+fn run_rescale(socket: UdpSocket, config: Config, config_path: PathBuf) -> Result<()> {
     let (send, recv) = channel::<Action>();
 
+    let bind_ip = local_ipv4();
     let mut scale = UnipolarFloat::ONE;
+    let mut last_sequence: HashMap<PortAddress, u8> = HashMap::new();
+    let mut synced_destinations: HashSet<Ipv4Addr> = HashSet::new();
+
+    let live = Arc::new(LiveConfig::new(&config));
+    live::install_reload_handler(Arc::clone(&live), config_path)?;
 
     let artnet_send = send.clone();
     let recv_socket = socket.try_clone().unwrap();
@@ -48,25 +95,39 @@ fn run_rescale(socket: UdpSocket, config: Config) -> Result<()> {
         }
     });
 
-    let send_osc = forward_osc(&config)?;
+    let tunnel = match &config.tunnel {
+        Some(tunnel_config) => Some(Arc::new(Tunnel::open(tunnel_config)?)),
+        None => None,
+    };
+    if let Some(tunnel) = &tunnel {
+        let tunnel = Arc::clone(tunnel);
+        let tunnel_send = send.clone();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            loop {
+                match receive_tunnel(&tunnel, &mut buffer) {
+                    Ok(Some(action)) => tunnel_send.send(action).unwrap(),
+                    Ok(None) => (),
+                    Err(err) => {
+                        error!("tunnel receive error: {err}");
+                    }
+                }
+            }
+        });
+    }
+
+    let send_osc = forward_osc(&config, Arc::clone(&live))?;
 
-    let input = Input::new(
-        config.midi_port.clone(),
-        config.rescale_midi_control,
-        send,
-        send_osc,
-    );
+    let input = Input::new(config.midi_port.clone(), Arc::clone(&live), send, send_osc);
     if let Err(err) = &input {
         error!("failed to open midi port: {err}");
     }
 
-    let actions = config.actions();
-
     loop {
         let action = recv.recv().unwrap();
         match action {
             Action::PollResp(addr) => {
-                let poll_resp = match poll_response() {
+                let poll_resp = match poll_response(&live, scale, bind_ip) {
                     Ok(msg) => msg,
                     Err(err) => {
                         error!("failed to create poll respose: {err}");
@@ -81,16 +142,31 @@ fn run_rescale(socket: UdpSocket, config: Config) -> Result<()> {
                 scale = val;
             }
             Action::Packet(mut output) => {
-                let Some(action) = actions.get(&output.port_address) else {
+                let Some(action) = live.action_for(&output.port_address) else {
                     debug!("Ignoring non-configured universe {:?}", output.port_address);
                     continue;
                 };
+                if output.sequence != 0 {
+                    let is_newer = match last_sequence.get(&output.port_address) {
+                        Some(&last) => is_newer_sequence(last, output.sequence),
+                        None => true,
+                    };
+                    if !is_newer {
+                        debug!(
+                            "Dropping out-of-order frame for {:?} (sequence {})",
+                            output.port_address, output.sequence
+                        );
+                        continue;
+                    }
+                    last_sequence.insert(output.port_address, output.sequence);
+                }
                 if action.rescale {
                     rescale_universe(scale, &mut output);
                 }
                 if !action.remap.is_empty() {
                     remap_universe(&action.remap, &mut output);
                 }
+                let destination = action.destination;
                 let command = ArtCommand::Output(output);
                 let buffer = match command.write_to_buffer() {
                     Ok(buf) => buf,
@@ -99,15 +175,53 @@ fn run_rescale(socket: UdpSocket, config: Config) -> Result<()> {
                         continue;
                     }
                 };
-                let dest = SocketAddrV4::new(action.destination, PORT);
+                if let Some(tunnel) = &tunnel {
+                    if let Err(err) = tunnel.send(&buffer) {
+                        error!("tunnel send error: {err}");
+                    }
+                }
+                let dest = SocketAddrV4::new(destination, PORT);
                 if let Err(err) = socket.send_to(&buffer, dest) {
                     error!("artnet send error: {err}");
+                } else {
+                    synced_destinations.insert(destination);
+                }
+            }
+            Action::Sync => {
+                if synced_destinations.is_empty() {
+                    continue;
+                }
+                let command = ArtCommand::Sync(Sync::default());
+                let buffer = match command.write_to_buffer() {
+                    Ok(buf) => buf,
+                    Err(err) => {
+                        error!("artnet sync serialization error: {err}");
+                        continue;
+                    }
+                };
+                if let Some(tunnel) = &tunnel {
+                    if let Err(err) = tunnel.send(&buffer) {
+                        error!("tunnel sync send error: {err}");
+                    }
+                }
+                for destination in synced_destinations.drain() {
+                    let dest = SocketAddrV4::new(destination, PORT);
+                    if let Err(err) = socket.send_to(&buffer, dest) {
+                        error!("artnet sync send error: {err}");
+                    }
                 }
             }
         }
     }
 }
 
+/// Compare two Art-Net sequence numbers under wrapped 1-255 ring
+/// arithmetic. A sequence of `0` means "not sequenced" and is handled by the
+/// caller before reaching this function.
+fn is_newer_sequence(last: u8, new: u8) -> bool {
+    new != last && (new.wrapping_sub(last)) & 0x80 == 0
+}
+
 fn rescale_universe(scale: UnipolarFloat, output: &mut Output) {
     for val in output.data.as_mut() {
         *val = ((*val as f64) * scale.val()) as u8;
@@ -131,6 +245,7 @@ pub enum Action {
     Scale(UnipolarFloat),
     Packet(Output),
     PollResp(SocketAddr),
+    Sync,
 }
 
 fn receive_artnet(socket: &UdpSocket, buffer: &mut [u8]) -> Result<Option<Action>> {
@@ -143,10 +258,30 @@ fn receive_artnet(socket: &UdpSocket, buffer: &mut [u8]) -> Result<Option<Action
             Ok(Some(Action::PollResp(addr)))
         }
         ArtCommand::Output(output) => Ok(Some(Action::Packet(output))),
+        ArtCommand::Sync(_) => Ok(Some(Action::Sync)),
         _ => Ok(None),
     }
 }
 
+/// Receive and decrypt one datagram from a tunnel peer. Output and Sync
+/// commands are handed off exactly as `receive_artnet` does for packets
+/// arriving on the LAN; ArtPoll and every other command are ignored.
+fn receive_tunnel(tunnel: &Tunnel, buffer: &mut [u8]) -> Result<Option<Action>> {
+    let length = tunnel.recv(buffer)?;
+    let Some(plaintext) = tunnel.decrypt(&buffer[..length])? else {
+        return Ok(None);
+    };
+    let command = ArtCommand::from_buffer(&plaintext)?;
+    match command {
+        ArtCommand::Output(output) => Ok(Some(Action::Packet(output))),
+        ArtCommand::Sync(_) => Ok(Some(Action::Sync)),
+        other => {
+            debug!("Ignoring unsupported command over tunnel: {other:?}");
+            Ok(None)
+        }
+    }
+}
+
 pub struct Input {
     _conn: MidiInputConnection<()>,
 }
@@ -154,7 +289,7 @@ pub struct Input {
 impl Input {
     pub fn new(
         name: String,
-        rescale_control: MidiControl,
+        live: Arc<LiveConfig>,
         sender: Sender<Action>,
         osc_forward: Sender<(MidiControl, u8)>,
     ) -> Result<Self> {
@@ -188,7 +323,7 @@ impl Input {
                     };
                     let val = msg[2];
                     // If this message matches scaler config, use it.
-                    if control == rescale_control {
+                    if control == live.rescale_control() {
                         sender.send(Action::Scale(unipolar_from_midi(val))).unwrap();
                         return;
                     }
@@ -225,11 +360,90 @@ pub enum EventType {
     ControlChange,
 }
 
-fn poll_response() -> Result<Vec<u8>> {
+/// Art-Net nodes only ever describe up to this many ports in a single
+/// ArtPollReply; if more universes than this are configured, advertise the
+/// first few and log the rest as hidden from discovery.
+const MAX_ADVERTISED_PORTS: usize = 4;
+
+/// Find the IPv4 address of the local interface that would be used to reach
+/// the network, by "connecting" a UDP socket and inspecting its local
+/// address. No traffic is actually sent.
+/// Best-effort discovery of the local IPv4 address to advertise in
+/// ArtPollReply. On an isolated lighting LAN with no route to the internet
+/// the probe connect below can fail even though the rescaler itself needs
+/// no internet reachability to relay Art-Net locally, so failure here is
+/// logged and falls back to advertising `Ipv4Addr::UNSPECIFIED` rather than
+/// preventing startup.
+fn local_ipv4() -> Ipv4Addr {
+    match probe_local_ipv4() {
+        Ok(addr) => addr,
+        Err(err) => {
+            warn!(
+                "could not determine local IPv4 address ({err}); ArtPollReply will advertise {}",
+                Ipv4Addr::UNSPECIFIED
+            );
+            Ipv4Addr::UNSPECIFIED
+        }
+    }
+}
+
+fn probe_local_ipv4() -> Result<Ipv4Addr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect(("8.8.8.8", 80))?;
+    match socket.local_addr()?.ip() {
+        std::net::IpAddr::V4(addr) => Ok(addr),
+        std::net::IpAddr::V6(addr) => bail!("local address {addr} is not IPv4"),
+    }
+}
+
+fn pad_to_64(s: &str) -> [u8; 64] {
+    let mut buf = [0u8; 64];
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(buf.len());
+    buf[..len].copy_from_slice(&bytes[..len]);
+    buf
+}
+
+fn poll_response(live: &LiveConfig, scale: UnipolarFloat, bind_ip: Ipv4Addr) -> Result<Vec<u8>> {
     let mut name = <[u8; 18]>::default();
     name[..8].copy_from_slice("rescaler".as_bytes());
+
+    let configured_universes = live.universes();
+    let mut universes: Vec<u8> = configured_universes.keys().copied().collect();
+    universes.sort_unstable();
+    if universes.len() > MAX_ADVERTISED_PORTS {
+        warn!(
+            "{} universes configured but ArtPollReply can only advertise {}; advertising the first {}",
+            universes.len(),
+            MAX_ADVERTISED_PORTS,
+            MAX_ADVERTISED_PORTS,
+        );
+        universes.truncate(MAX_ADVERTISED_PORTS);
+    }
+
+    let mut port_types = [0u8; 4];
+    let mut good_output = [0u8; 4];
+    let mut swin = [0u8; 4];
+    let mut swout = [0u8; 4];
+    for (port, universe) in universes.iter().enumerate() {
+        port_types[port] = 0x80; // port configured, DMX512 output
+        good_output[port] = 0x80; // data is being transmitted
+        swin[port] = *universe;
+        swout[port] = *universe;
+    }
+
+    let remap_count: usize = universes
+        .iter()
+        .filter_map(|id| configured_universes.get(id))
+        .map(|action| action.remap.len())
+        .sum();
+    let description = format!(
+        "scale={:.2} universes={universes:?} remaps={remap_count}",
+        scale.val(),
+    );
+
     let resp = ArtCommand::PollReply(Box::new(PollReply {
-        address: Ipv4Addr::new(1, 1, 1, 1),
+        address: bind_ip,
         port: PORT,
         version: Default::default(),
         port_address: Default::default(),
@@ -238,21 +452,21 @@ fn poll_response() -> Result<Vec<u8>> {
         status_1: 0,
         esta_code: 0,
         short_name: name,
-        long_name: [0; 64],
-        node_report: [0; 64],
-        num_ports: Default::default(),
-        port_types: Default::default(),
+        long_name: pad_to_64(&description),
+        node_report: pad_to_64(&description),
+        num_ports: [0, universes.len() as u8],
+        port_types,
         good_input: Default::default(),
-        good_output: Default::default(),
-        swin: Default::default(),
-        swout: Default::default(),
+        good_output,
+        swin,
+        swout,
         sw_video: Default::default(),
         sw_macro: Default::default(),
         sw_remote: Default::default(),
         spare: Default::default(),
         style: Default::default(),
         mac: Default::default(),
-        bind_ip: Default::default(),
+        bind_ip,
         bind_index: Default::default(),
         status_2: Default::default(),
         filler: Default::default(),
@@ -260,12 +474,14 @@ fn poll_response() -> Result<Vec<u8>> {
     Ok(resp.write_to_buffer()?)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Config {
     midi_port: String,
     rescale_midi_control: MidiControl,
     osc_forward: OscForward,
     universes: HashMap<u8, UniverseActions>,
+    #[serde(default)]
+    tunnel: Option<tunnel::TunnelConfig>,
 }
 
 impl Config {
@@ -277,62 +493,59 @@ impl Config {
     }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct UniverseActions {
     #[serde(default)]
     rescale: bool,
     #[serde(default)]
     remap: Vec<Remapping>,
+    /// A unicast address, the Art-Net broadcast address
+    /// (e.g. `2.255.255.255`), or an IPv4 multicast group. Broadcast and
+    /// multicast destinations are handled by `bind_output_socket`.
     destination: Ipv4Addr,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Remapping {
     start: usize,
     length: usize,
     new_start: usize,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Hash)]
 pub struct MidiControl {
     channel: u8,
     control: u8,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
 pub struct OscMapping {
     midi: MidiControl,
     osc: String,
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
 pub struct OscForward {
     destination: SocketAddr,
     mappings: Vec<OscMapping>,
 }
 
-fn forward_osc(config: &Config) -> Result<Sender<(MidiControl, u8)>> {
+fn forward_osc(config: &Config, live: Arc<LiveConfig>) -> Result<Sender<(MidiControl, u8)>> {
     let (send, recv) = channel();
 
     let dest = config.osc_forward.destination;
-    let mapping: HashMap<_, _> = config
-        .osc_forward
-        .mappings
-        .iter()
-        .map(|mapping| (mapping.midi, mapping.osc.clone()))
-        .collect();
 
     let socket = UdpSocket::bind("0.0.0.0:0")?;
 
     thread::spawn(move || loop {
         let (midi_control, val) = recv.recv().unwrap();
-        let Some(osc_control) = mapping.get(&midi_control) else {
+        let Some(osc_control) = live.osc_address_for(&midi_control) else {
                 warn!("ignoring unmapped midi mapping {:?}", midi_control);
                 continue;
             };
         let payload = unipolar_from_midi(val);
         let osc_msg = OscMessage {
-            addr: osc_control.clone(),
+            addr: osc_control,
             args: vec![OscType::Double(payload.val())],
         };
 
@@ -0,0 +1,201 @@
+//! Optional encrypted relay mode for shipping DMX between two rescaler
+//! instances over an untrusted network (e.g. venue to remote site). When no
+//! `tunnel` section is present in the config this module is unused and
+//! cleartext LAN operation is exactly as before.
+
+use anyhow::{anyhow, bail, Result};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// XChaCha20-Poly1305 uses a 192-bit nonce, so it is drawn fresh at random
+/// for every packet rather than from a counter: a 24-byte random nonce can
+/// be reused across process restarts without the catastrophic key/nonce
+/// collision that a short counter-derived nonce would suffer. Replay
+/// protection is handled separately below, by an explicit, authenticated
+/// counter that travels alongside the nonce.
+const NONCE_LEN: usize = 24;
+const COUNTER_LEN: usize = 8;
+
+/// How far behind the highest counter seen so far a packet's counter may
+/// fall and still be accepted; anything older is assumed to be a replay.
+const REPLAY_WINDOW: u64 = 1024;
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TunnelConfig {
+    /// Local address to bind for sending to and receiving from the peer.
+    bind: SocketAddr,
+    /// The remote rescaler instance's tunnel address.
+    peer: SocketAddr,
+    #[serde(flatten)]
+    key: TunnelKey,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum TunnelKey {
+    Key { key: [u8; 32] },
+    Passphrase { passphrase: String },
+}
+
+impl TunnelKey {
+    fn derive(&self) -> [u8; 32] {
+        match self {
+            TunnelKey::Key { key } => *key,
+            TunnelKey::Passphrase { passphrase } => {
+                let mut hasher = Sha256::new();
+                hasher.update(passphrase.as_bytes());
+                hasher.finalize().into()
+            }
+        }
+    }
+}
+
+/// Sliding replay window over the monotonically increasing, authenticated
+/// send counter carried alongside each packet's random nonce.
+struct ReplayWindow {
+    highest: Option<u64>,
+    recent: HashSet<u64>,
+}
+
+impl ReplayWindow {
+    fn new() -> Self {
+        Self {
+            highest: None,
+            recent: HashSet::new(),
+        }
+    }
+
+    /// Non-mutating test: would `counter` be accepted right now (not too
+    /// old relative to the highest counter seen, not already seen)? Safe to
+    /// call before the packet's AEAD tag has been verified, since it
+    /// records nothing.
+    fn would_accept(&self, counter: u64) -> bool {
+        if let Some(highest) = self.highest {
+            if highest.saturating_sub(counter) >= REPLAY_WINDOW {
+                return false;
+            }
+        }
+        !self.recent.contains(&counter)
+    }
+
+    /// Record `counter` as seen. Must only be called once the packet
+    /// carrying it has been authenticated — committing an unauthenticated
+    /// counter (e.g. one taken from a forged datagram) would let an
+    /// attacker without the key bump `highest` arbitrarily high and
+    /// permanently reject every legitimate packet after it.
+    fn commit(&mut self, counter: u64) {
+        self.recent.insert(counter);
+        let highest = *self.highest.get_or_insert(counter);
+        if counter > highest {
+            self.highest = Some(counter);
+        }
+        let floor = self.highest.unwrap().saturating_sub(REPLAY_WINDOW);
+        self.recent.retain(|seen| *seen > floor);
+    }
+}
+
+/// An encrypted point-to-point link to another rescaler instance, carrying
+/// raw Art-Net datagrams wrapped in XChaCha20-Poly1305.
+pub struct Tunnel {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    cipher: XChaCha20Poly1305,
+    send_counter: AtomicU64,
+    replay_window: Mutex<ReplayWindow>,
+}
+
+impl Tunnel {
+    pub fn open(config: &TunnelConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(config.bind)?;
+        let key = Key::from_slice(&config.key.derive());
+        Ok(Self {
+            socket,
+            peer: config.peer,
+            cipher: XChaCha20Poly1305::new(key),
+            send_counter: AtomicU64::new(0),
+            replay_window: Mutex::new(ReplayWindow::new()),
+        })
+    }
+
+    /// Encrypt `plaintext` (a serialized `ArtCommand`) and send it to the peer.
+    pub fn send(&self, plaintext: &[u8]) -> Result<()> {
+        let counter = self.send_counter.fetch_add(1, Ordering::Relaxed);
+        let counter_bytes = counter.to_be_bytes();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &counter_bytes,
+                },
+            )
+            .map_err(|err| anyhow!("tunnel encryption error: {err}"))?;
+        let mut packet = Vec::with_capacity(nonce.len() + COUNTER_LEN + ciphertext.len());
+        packet.extend_from_slice(&nonce);
+        packet.extend_from_slice(&counter_bytes);
+        packet.extend_from_slice(&ciphertext);
+        self.socket.send_to(&packet, self.peer)?;
+        Ok(())
+    }
+
+    /// Block waiting for the next raw datagram from the tunnel socket.
+    pub fn recv(&self, buffer: &mut [u8]) -> Result<usize> {
+        let (length, _addr) = self.socket.recv_from(buffer)?;
+        Ok(length)
+    }
+
+    /// Verify and decrypt a datagram received from the tunnel socket,
+    /// rejecting stale or replayed counters. Returns `None` if the packet
+    /// was dropped as a replay (already logged).
+    pub fn decrypt(&self, datagram: &[u8]) -> Result<Option<Vec<u8>>> {
+        if datagram.len() < NONCE_LEN + COUNTER_LEN {
+            bail!(
+                "tunnel packet shorter than nonce + counter ({} bytes)",
+                datagram.len()
+            );
+        }
+        let (nonce_bytes, rest) = datagram.split_at(NONCE_LEN);
+        let (counter_bytes, ciphertext) = rest.split_at(COUNTER_LEN);
+        let counter = u64::from_be_bytes(counter_bytes.try_into().unwrap());
+
+        // The counter travels in the clear (authenticated only via AAD), so
+        // an attacker without the key can put any value here. Only *test*
+        // the window before the tag is verified; commit the counter as seen
+        // only once decryption below proves it came from the real peer.
+        let mut replay_window = self.replay_window.lock().unwrap();
+        if !replay_window.would_accept(counter) {
+            warn!("dropping replayed or stale tunnel packet (counter {counter})");
+            return Ok(None);
+        }
+
+        let nonce = XNonce::from_slice(nonce_bytes);
+        let plaintext = self
+            .cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: counter_bytes,
+                },
+            )
+            .map_err(|err| anyhow!("tunnel decryption error: {err}"))?;
+
+        replay_window.commit(counter);
+        Ok(Some(plaintext))
+    }
+}
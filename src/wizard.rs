@@ -0,0 +1,262 @@
+//! Interactive setup wizard for producing a rescaler config YAML.
+//!
+//! Discovers reachable Art-Net nodes with an ArtPoll/ArtPollReply exchange,
+//! enumerates local MIDI input ports, and walks the user through building a
+//! `Config` via arrow-key prompts instead of requiring them to hand-author
+//! YAML against the struct definitions.
+
+use crate::{Config, MidiControl, OscForward, Remapping, UniverseActions, PORT};
+use anyhow::Result;
+use artnet_protocol::{ArtCommand, Poll, PollReply};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input as TextInput, MultiSelect, Select};
+use midir::MidiInput;
+use std::{
+    collections::HashMap,
+    fs::File,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for ArtPollReply packets after broadcasting our poll.
+const DISCOVERY_WINDOW: Duration = Duration::from_secs(3);
+
+/// A node discovered on the network, along with the universes it reports
+/// handling.
+struct DiscoveredNode {
+    address: Ipv4Addr,
+    name: String,
+    universes: Vec<u8>,
+}
+
+/// Run the interactive wizard, writing a complete `Config` out to `output_path`.
+pub fn run(output_path: &Path) -> Result<()> {
+    println!("Rescaler setup wizard");
+    println!("=====================");
+
+    let nodes = discover_nodes()?;
+    if nodes.is_empty() {
+        println!("No Art-Net nodes responded to discovery; you can still configure universes by hand.");
+    } else {
+        println!("Discovered {} node(s):", nodes.len());
+        for node in &nodes {
+            println!(
+                "  {} ({}) - universes {:?}",
+                node.name, node.address, node.universes
+            );
+        }
+    }
+
+    let midi_port = select_midi_port()?;
+    let rescale_midi_control = prompt_midi_control("rescale control")?;
+
+    let universes = configure_universes(&nodes)?;
+    let osc_forward = configure_osc_forward()?;
+
+    let config = Config {
+        midi_port,
+        rescale_midi_control,
+        osc_forward,
+        universes,
+        tunnel: None,
+    };
+
+    let output_file = File::create(output_path)?;
+    serde_yaml::to_writer(output_file, &config)?;
+    println!("Wrote config to {}", output_path.display());
+    Ok(())
+}
+
+/// Broadcast an ArtPoll and collect ArtPollReply packets for a few seconds.
+fn discover_nodes() -> Result<Vec<DiscoveredNode>> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_broadcast(true)?;
+    socket.set_read_timeout(Some(Duration::from_millis(250)))?;
+
+    let poll = ArtCommand::Poll(Poll::default());
+    socket.send_to(&poll.write_to_buffer()?, ("255.255.255.255", PORT))?;
+
+    let mut nodes = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let deadline = Instant::now() + DISCOVERY_WINDOW;
+    while Instant::now() < deadline {
+        match socket.recv_from(&mut buffer) {
+            Ok((length, SocketAddr::V4(addr))) => {
+                if let Ok(ArtCommand::PollReply(reply)) = ArtCommand::from_buffer(&buffer[..length]) {
+                    nodes.push(node_from_reply(*addr.ip(), &reply));
+                }
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(nodes)
+}
+
+fn node_from_reply(address: Ipv4Addr, reply: &PollReply) -> DiscoveredNode {
+    let name = String::from_utf8_lossy(&reply.short_name)
+        .trim_end_matches('\0')
+        .to_string();
+    // A port slot's value of `0` in `swin`/`swout` is not a sentinel for
+    // "unused" (universe 0 is a normal universe); whether the slot is
+    // actually in use is the `0x80` bit in `port_types`, same as
+    // `poll_response` sets it in main.rs.
+    let universes = reply
+        .port_types
+        .iter()
+        .enumerate()
+        .filter(|(_, port_type)| *port_type & 0x80 != 0)
+        .flat_map(|(port, _)| [reply.swin[port], reply.swout[port]])
+        .collect();
+    DiscoveredNode {
+        address,
+        name,
+        universes,
+    }
+}
+
+fn select_midi_port() -> Result<String> {
+    let input = MidiInput::new("rescaler wizard")?;
+    let ports = input.ports();
+    let names = ports
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        anyhow::bail!("no MIDI input ports found");
+    }
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select the MIDI control surface")
+        .items(&names)
+        .default(0)
+        .interact()?;
+    Ok(names[selection].clone())
+}
+
+fn prompt_midi_control(label: &str) -> Result<MidiControl> {
+    let channel: u8 = TextInput::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("MIDI channel for {label}"))
+        .interact_text()?;
+    let control: u8 = TextInput::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("MIDI control number for {label}"))
+        .interact_text()?;
+    Ok(MidiControl { channel, control })
+}
+
+fn configure_universes(nodes: &[DiscoveredNode]) -> Result<HashMap<u8, UniverseActions>> {
+    let mut universes = HashMap::new();
+
+    let candidate_universes: Vec<u8> = {
+        let mut all: Vec<u8> = nodes.iter().flat_map(|node| node.universes.clone()).collect();
+        all.sort_unstable();
+        all.dedup();
+        all
+    };
+
+    let selected = if candidate_universes.is_empty() {
+        Vec::new()
+    } else {
+        let labels = candidate_universes
+            .iter()
+            .map(|universe| format!("universe {universe}"))
+            .collect::<Vec<_>>();
+        let picked = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select universes to rescale/remap (space to toggle)")
+            .items(&labels)
+            .interact()?;
+        picked
+            .into_iter()
+            .map(|index| candidate_universes[index])
+            .collect()
+    };
+
+    let universe_ids = if selected.is_empty() {
+        let mut ids = Vec::new();
+        loop {
+            let id: u8 = TextInput::with_theme(&ColorfulTheme::default())
+                .with_prompt("Universe to configure (0-255)")
+                .interact_text()?;
+            ids.push(id);
+            if !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Add another universe?")
+                .default(false)
+                .interact()?
+            {
+                break ids;
+            }
+        }
+    } else {
+        selected
+    };
+
+    for universe in universe_ids {
+        let rescale = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Rescale universe {universe}?"))
+            .default(true)
+            .interact()?;
+        let remap = configure_remappings()?;
+        let destination: Ipv4Addr = TextInput::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Destination IP for universe {universe}"))
+            .interact_text()?;
+        universes.insert(
+            universe,
+            UniverseActions {
+                rescale,
+                remap,
+                destination,
+            },
+        );
+    }
+
+    Ok(universes)
+}
+
+fn configure_remappings() -> Result<Vec<Remapping>> {
+    let mut remappings = Vec::new();
+    while Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Add a channel remapping?")
+        .default(false)
+        .interact()?
+    {
+        let start: usize = TextInput::with_theme(&ColorfulTheme::default())
+            .with_prompt("Source start channel")
+            .interact_text()?;
+        let length: usize = TextInput::with_theme(&ColorfulTheme::default())
+            .with_prompt("Number of channels")
+            .interact_text()?;
+        let new_start: usize = TextInput::with_theme(&ColorfulTheme::default())
+            .with_prompt("Destination start channel")
+            .interact_text()?;
+        remappings.push(Remapping {
+            start,
+            length,
+            new_start,
+        });
+    }
+    Ok(remappings)
+}
+
+fn configure_osc_forward() -> Result<OscForward> {
+    let destination: SocketAddr = TextInput::with_theme(&ColorfulTheme::default())
+        .with_prompt("OSC forwarding destination (host:port)")
+        .interact_text()?;
+
+    let mut mappings = Vec::new();
+    while Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Add an OSC mapping for a MIDI control?")
+        .default(false)
+        .interact()?
+    {
+        let midi = prompt_midi_control("this mapping")?;
+        let osc: String = TextInput::with_theme(&ColorfulTheme::default())
+            .with_prompt("OSC address")
+            .interact_text()?;
+        mappings.push(crate::OscMapping { midi, osc });
+    }
+
+    Ok(OscForward {
+        destination,
+        mappings,
+    })
+}